@@ -1,27 +1,146 @@
 use serde::{Serialize, Deserialize};
 use serde_json;
-use reqwest::{self, Client, StatusCode, header::{ACCEPT, HeaderValue}};
+use reqwest::{self, Client, StatusCode, header::{ACCEPT, RANGE, HeaderValue, InvalidHeaderValue}};
+use flate2::read::GzDecoder;
+use sha1::{Sha1, Digest as _};
+use sha2::Sha256;
+use std::error;
 use std::fmt;
+use std::io::Read;
 
-pub type Result<T> = std::result::Result<T, String>;
+pub type Result<T> = std::result::Result<T, CommonerError>;
 
 /// `CDX_HOST` is the host for accessing cdx index data.
 pub const CDX_HOST: &str = "index.commoncrawl.org";
 /// `WARC_HOST` is the host for accessing WARC data.
 pub const WARC_HOST: &str = "commoncrawl.s3.amazonaws.com";
 
+/// `KNOWN_CHARSETS` lists the charset strings `Charset::from_string` accepts,
+/// used to build "did you mean" suggestions for typos.
+const KNOWN_CHARSETS: &[&str] = &["utf-8", "utf-16"];
+
+/// `KNOWN_CONTENT_TYPES` lists the content-type essences `ContentType::from_string`
+/// recognises by name, used to build "did you mean" suggestions for typos.
+const KNOWN_CONTENT_TYPES: &[&str] = &["application/json", "text/plain", "application/warc", "application/x-gzip"];
+
+/// `CommonerError` is the error type returned by fallible operations in
+/// this crate. It replaces an earlier `String`-based error so that
+/// callers can match on failures instead of only displaying them.
+#[derive(Debug)]
+pub enum CommonerError {
+    /// `Http` is returned when a fetch completes with an unexpected status code.
+    Http { status: StatusCode },
+    /// `Transport` wraps a network-level error from the underlying HTTP client.
+    Transport(reqwest::Error),
+    /// `Json` wraps a JSON (de)serialization error.
+    Json(serde_json::Error),
+    /// `InvalidUrl` is returned when a string cannot be parsed into a `Url`.
+    InvalidUrl(String),
+    /// `UnknownDomain` is returned when a `Url` is parsed against a host this crate doesn't serve.
+    UnknownDomain(String),
+    /// `InvalidCharset` is returned when a charset string doesn't match a known `Charset`.
+    InvalidCharset(String),
+    /// `InvalidContentType` is returned when a content-type string can't be parsed.
+    InvalidContentType(String),
+    /// `InvalidDigest` is returned when a `CDXItem` digest can't be decoded.
+    InvalidDigest(String),
+    /// `Other` carries a free-form error message for cases that don't fit the above.
+    Other(String),
+}
+
+impl fmt::Display for CommonerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommonerError::Http { status } => write!(f, "unexpected status code: {}", status),
+            CommonerError::Transport(e) => write!(f, "transport error: {}", e),
+            CommonerError::Json(e) => write!(f, "json error: {}", e),
+            CommonerError::InvalidUrl(s) => write!(f, "invalid url: {}", s),
+            CommonerError::UnknownDomain(s) => write!(f, "unknown domain: {}", s),
+            CommonerError::InvalidCharset(s) => {
+                write!(f, "invalid charset '{}'", s)?;
+                if let Some(suggestion) = nearest_match(s, KNOWN_CHARSETS) {
+                    write!(f, ", did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            },
+            CommonerError::InvalidContentType(s) => {
+                write!(f, "invalid content-type '{}'", s)?;
+                if let Some(suggestion) = nearest_match(s, KNOWN_CONTENT_TYPES) {
+                    write!(f, ", did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            },
+            CommonerError::InvalidDigest(s) => write!(f, "invalid digest: {}", s),
+            CommonerError::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl error::Error for CommonerError {}
+
+impl From<reqwest::Error> for CommonerError {
+    fn from(e: reqwest::Error) -> CommonerError {
+        CommonerError::Transport(e)
+    }
+}
+
+impl From<serde_json::Error> for CommonerError {
+    fn from(e: serde_json::Error) -> CommonerError {
+        CommonerError::Json(e)
+    }
+}
+
+impl From<InvalidHeaderValue> for CommonerError {
+    fn from(e: InvalidHeaderValue) -> CommonerError {
+        CommonerError::Other(format!("{}", e))
+    }
+}
+
+/// `nearest_match` returns the entry in `known` closest to `input` by edit
+/// distance, if one is close enough to plausibly be a typo of it.
+fn nearest_match<'a>(input: &str, known: &[&'a str]) -> Option<&'a str> {
+    known.iter()
+        .map(|candidate| (*candidate, levenshtein(input, candidate)))
+        .min_by_key(|(_, dist)| *dist)
+        .filter(|(_, dist)| *dist <= 2)
+        .map(|(candidate, _)| candidate)
+}
+
+/// `levenshtein` computes the edit distance between two strings.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut prev = row[0];
+        row[0] = i;
+
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let deletion = row[j] + 1;
+            let insertion = row[j - 1] + 1;
+            let substitution = prev + cost;
+
+            prev = row[j];
+            row[j] = deletion.min(insertion).min(substitution);
+        }
+    }
+
+    row[b.len()]
+}
+
 /// `ToJson` specifies the operations implemented by types that can be serialized into JSON.
 pub trait ToJson<'a>: Serialize + Deserialize<'a> {
     /// `to_json_string` serializes the implementor into a json string.
     fn to_json_string(&self) -> Result<String> {
-        serde_json::to_string(self)
-            .map_err(|e| format!("{}", e))
+        Ok(serde_json::to_string(self)?)
     }
-    
+
     /// `to_json_bytes` serializes the implementor into json bytes.
     fn to_json_bytes(&self) -> Result<Vec<u8>> {
-        serde_json::to_vec(self)
-            .map_err(|e| format!("{}", e))
+        Ok(serde_json::to_vec(self)?)
     }
 }
 
@@ -29,14 +148,12 @@ pub trait ToJson<'a>: Serialize + Deserialize<'a> {
 pub trait FromJson<'a>: Serialize + Deserialize<'a> {
     /// `from_json_string` deserializes an instance of the implementor from a json string.
     fn from_json_string(s: &'a str) -> Result<Self> {
-        serde_json::from_str(s)
-            .map_err(|e| format!("{}", e))
+        Ok(serde_json::from_str(s)?)
     }
 
     /// `from_json_bytes` deserializes an instance of the implementor from json bytes.
     fn from_json_bytes(b: &'a [u8]) -> Result<Self> {
-        serde_json::from_slice(b)
-            .map_err(|e| format!("{}", e))
+        Ok(serde_json::from_slice(b)?)
     }
 }
 
@@ -56,12 +173,13 @@ impl Url {
     /// `from_string` creates a `Url` from a string.
     pub fn from_string(s: &str) -> Result<Url> {
         let url = reqwest::Url::parse(s)
-            .map_err(|e| format!("{}", e))?;
+            .map_err(|_| CommonerError::InvalidUrl(s.to_string()))?;
 
         match url.host_str() {
             Some(CDX_HOST) => Ok(Url::CDX { path: url.path().into() }),
             Some(WARC_HOST) => Ok(Url::WARC { path: url.path().into() }),
-            _ => Err("invalid domain".into())
+            Some(host) => Err(CommonerError::UnknownDomain(host.to_string())),
+            None => Err(CommonerError::InvalidUrl(s.to_string())),
         }
     }
 }
@@ -81,7 +199,10 @@ impl fmt::Display for Url {
    }
 }
 
-/// `Charset` is the set of charsets used by `ContentType`.
+/// `Charset` is the set of charsets used by `ContentType`. Marked
+/// `#[non_exhaustive]` since Common Crawl responses may use charsets
+/// beyond the ones this crate currently matches on.
+#[non_exhaustive]
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum Charset {
     UTF8,
@@ -94,12 +215,13 @@ impl Charset {
        format!("{}", self)
     }
 
-    /// `from_string` creates a `Charset` from a string.
+    /// `from_string` creates a `Charset` from a string. Matching is
+    /// case-insensitive and surrounding whitespace is trimmed.
     pub fn from_string(s: &str) -> Result<Charset> {
-        match s {
+        match s.trim().to_lowercase().as_str() {
             "utf-8" => Ok(Charset::UTF8),
             "utf-16" => Ok(Charset::UTF16),
-            _ => Err("invalid charset".into())
+            _ => Err(CommonerError::InvalidCharset(s.to_string()))
         }
     }
 }
@@ -119,26 +241,69 @@ impl fmt::Display for Charset {
    }
 }
 
-/// `ContentType` is the set of content-types used by `Fetcher`.
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+/// `ContentType` is the set of content-types used by `Fetcher`. Marked
+/// `#[non_exhaustive]` and carrying an `Other` catch-all so that callers
+/// can match on whatever content-type a server actually sends back
+/// instead of `from_string` erroring out on it.
+#[non_exhaustive]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum ContentType {
     JSON,
     TEXT { charset: Charset },
+    WARC,
+    GZIP,
+    Other(String),
 }
 
 impl ContentType {
     /// `to_string` returns the `ContentType` string.
-    pub fn to_string(self) -> String {
+    pub fn to_string(&self) -> String {
        format!("{}", self)
     }
 
-    /// `from_string` creates a `ContentType` from a string.
+    /// `from_string` creates a `ContentType` from a string. The essence
+    /// (`type/subtype`) is split off from any `;`-separated parameters,
+    /// matched case-insensitively. For `text/plain` the `charset`
+    /// parameter is read when present (an unknown charset is an
+    /// `InvalidCharset` error) and defaults to UTF-8; other essences
+    /// ignore it. Essences this crate doesn't otherwise recognise fall
+    /// back to `Other` rather than erroring, since real servers send a
+    /// wide variety of them.
     pub fn from_string(s: &str) -> Result<ContentType> {
-        match s {
-            "application/json" => Ok(ContentType::JSON ),
-            "text/plain; charset=utf-8" => Ok(ContentType::TEXT { charset: Charset::UTF8 } ),
-            "text/plain; charset=utf-16" => Ok(ContentType::TEXT { charset: Charset::UTF16 } ),
-            _ => Err("invalid content-type".into())
+        let mut parts = s.split(';');
+
+        let essence = parts.next()
+            .unwrap_or("")
+            .trim()
+            .to_lowercase();
+
+        if essence.is_empty() {
+            return Err(CommonerError::InvalidContentType(s.to_string()));
+        }
+
+        let mut charset = None;
+        for param in parts {
+            let mut kv = param.splitn(2, '=');
+            let key = kv.next().unwrap_or("").trim().to_lowercase();
+            let value = kv.next().unwrap_or("").trim().trim_matches('"');
+
+            if key == "charset" {
+                charset = Some(value);
+            }
+        }
+
+        match essence.as_str() {
+            "application/json" => Ok(ContentType::JSON),
+            "text/plain" => {
+                let charset = match charset {
+                    Some(value) => Charset::from_string(value)?,
+                    None => Charset::default(),
+                };
+                Ok(ContentType::TEXT { charset })
+            },
+            "application/warc" => Ok(ContentType::WARC),
+            "application/x-gzip" => Ok(ContentType::GZIP),
+            _ => Ok(ContentType::Other(essence)),
         }
     }
 }
@@ -156,6 +321,9 @@ impl fmt::Display for ContentType {
            ContentType::TEXT { charset } => {
                write!(f, "text/plain; charset={}", charset)
            },
+           ContentType::WARC => write!(f, "application/warc"),
+           ContentType::GZIP => write!(f, "application/x-gzip"),
+           ContentType::Other(essence) => write!(f, "{}", essence),
        }
    }
 }
@@ -191,115 +359,371 @@ impl Fetcher {
 
     /// `exec` execs the `Fetcher`.
     pub fn exec(self) -> Result<Vec<u8>> {
-        let content_type = HeaderValue::from_str(&self.content_type.to_string())
-            .map_err(|e| format!("{}", e))?;
+        let content_type = HeaderValue::from_str(&self.content_type.to_string())?;
 
         let req_builder = Client::new().get(&self.url.to_string());
 
         let mut res = req_builder
             .header(ACCEPT, content_type)
-            .send()
-            .map_err(|e| format!("{}", e))?;
+            .send()?;
 
         if res.status() != StatusCode::OK {
-            return Err(format!("status code: {}", res.status()));
+            return Err(CommonerError::Http { status: res.status() });
         }
 
         let mut contents = Vec::new();
-        res.copy_to(&mut contents)
-            .map_err(|e| format!("{}", e))?;
+        res.copy_to(&mut contents)?;
 
         Ok(contents)
     }
 }
 
 /// `CDXQuerier` is used to query the CommonCrawl Index CDX API.
-#[derive(Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
+///
+/// Serializing a `CDXQuerier` (e.g. for request logging or an on-disk
+/// cache key) omits fields left at their default, so the serialized form
+/// only reflects the parameters the caller actually set.
+#[derive(Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CDXQuerier {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub index: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub path: String,
+    #[serde(skip_serializing_if = "is_zero_u64")]
     pub from: u64,
+    #[serde(skip_serializing_if = "is_zero_u64")]
     pub to: u64,
+    #[serde(skip_serializing_if = "is_zero_u64")]
     pub limit: u64,
+    #[serde(skip_serializing_if = "is_zero_i64")]
     pub sort: i64,
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub filter: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub field: Option<String>,
+    #[serde(skip_serializing_if = "is_zero_u64")]
     pub page: u64,
+    #[serde(skip_serializing_if = "is_zero_u64")]
     pub page_size: u64,
+    #[serde(skip_serializing_if = "is_false")]
     pub show_num_pages: bool,
+    #[serde(skip_serializing_if = "is_false")]
     pub show_paged_index: bool,
 }
 
+/// `is_zero_u64` reports whether `n` is the default `u64` value, used to
+/// skip serializing unset numeric fields on `CDXQuerier`.
+fn is_zero_u64(n: &u64) -> bool {
+    *n == 0
+}
+
+/// `is_zero_i64` reports whether `n` is the default `i64` value, used to
+/// skip serializing unset numeric fields on `CDXQuerier`.
+fn is_zero_i64(n: &i64) -> bool {
+    *n == 0
+}
+
+/// `is_false` reports whether `b` is the default `bool` value, used to
+/// skip serializing unset toggle fields on `CDXQuerier`.
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// `pad_timestamp` pads a CDX API timestamp of fewer than 14 digits with
+/// `pad` up to 14 digits, so that a partial timestamp such as `2020` can
+/// be used as either the lower (`pad = b'0'`) or upper (`pad = b'9'`)
+/// bound of a date/time range.
+fn pad_timestamp(ts: u64, pad: u8) -> Result<u64> {
+    let digits = ts.to_string();
+
+    if digits.len() > 14 {
+        return Err(CommonerError::Other(format!("timestamp '{}' has more than 14 digits", ts)));
+    }
+
+    let mut padded = digits.into_bytes();
+    padded.resize(14, pad);
+
+    let padded = String::from_utf8(padded)
+        .map_err(|e| CommonerError::Other(format!("{}", e)))?;
+
+    padded.parse::<u64>()
+        .map_err(|e| CommonerError::Other(format!("{}", e)))
+}
+
+/// `encode_query_params` percent-encodes `params` into a query string,
+/// so that values containing characters significant to CDX url-match or
+/// filter syntax (`*`, spaces, `&`, ...) don't corrupt the request.
+fn encode_query_params(params: &[(String, String)]) -> String {
+    let mut url = reqwest::Url::parse("http://cdx.invalid/")
+        .expect("static base url is valid");
+
+    url.query_pairs_mut().extend_pairs(params);
+
+    url.query().unwrap_or("").to_string()
+}
+
+/// `NumPagesResponse` is the shape of a `showNumPages` response under
+/// `output=json`, which is a JSON object rather than the bare integer
+/// the API returns when `output=json` is omitted. Other fields the CDX
+/// server includes (`pageSize`, `blocks`) are ignored.
+#[derive(Deserialize)]
+struct NumPagesResponse {
+    pages: u64,
+}
+
 impl CDXQuerier {
     /// `new` creates a new CDXQuerier.
     pub fn new() -> CDXQuerier {
         CDXQuerier::default()
     }
 
-    /// `set_path` sets the path of the collection index.
-    pub fn set_path(_path: &str) -> Result<CDXQuerier> {
-        unreachable!()
+    /// `set_index` sets the collection index endpoint to query, i.e. the
+    /// path segment identifying one Common Crawl collection's CDX API
+    /// (e.g. `CC-MAIN-2024-10-index`), as published in
+    /// `CollectionInfo::cdx_api`. Prefer `for_collection`, which derives
+    /// this from a `CollectionInfo` directly.
+    pub fn set_index(mut self, index: &str) -> Result<CDXQuerier> {
+        let index = index.trim().trim_start_matches('/');
+
+        if index.is_empty() {
+            return Err(CommonerError::Other("empty index".into()));
+        }
+
+        self.index = index.into();
+        Ok(self)
+    }
+
+    /// `for_collection` creates a `CDXQuerier` targeting `info`'s CDX API
+    /// endpoint, parsing the collection index path out of its `cdx_api` URL.
+    pub fn for_collection(info: &CollectionInfo) -> Result<CDXQuerier> {
+        match Url::from_string(&info.cdx_api)? {
+            Url::CDX { path } => CDXQuerier::new().set_index(&path),
+            _ => Err(CommonerError::Other(format!("'{}' is not a CDX API endpoint", info.cdx_api))),
+        }
+    }
+
+    /// `set_path` sets the path of the collection index, i.e. the url or
+    /// url pattern to look up in the index.
+    pub fn set_path(mut self, path: &str) -> Result<CDXQuerier> {
+        let path = path.trim();
+
+        if path.is_empty() {
+            return Err(CommonerError::Other("empty path".into()));
+        }
+
+        self.path = path.into();
+        Ok(self)
     }
 
     /// `set_from` sets the from timestamp in the date/time range of the query.
     /// The value has to have less than 14 digits and will be padded to the
     /// lower bound.
-    pub fn set_from(_from: u64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_from(mut self, from: u64) -> Result<CDXQuerier> {
+        self.from = pad_timestamp(from, b'0')?;
+        Ok(self)
     }
 
     /// `set_to` sets the to timestamp in the date/time range of the query.
     /// The value has to have less than 14 digits and will be padded to the
     /// upper bound.
-    pub fn set_to(_to: u64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_to(mut self, to: u64) -> Result<CDXQuerier> {
+        self.to = pad_timestamp(to, b'9')?;
+        Ok(self)
     }
 
     /// `set_limit` sets the limit to the number of returned items from the query.
-    pub fn set_limit(_limit: u64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_limit(mut self, limit: u64) -> Result<CDXQuerier> {
+        self.limit = limit;
+        Ok(self)
     }
 
     /// `set_sort` sets the sorting method in the query.
-    pub fn set_sort(_sort: i64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_sort(mut self, sort: i64) -> Result<CDXQuerier> {
+        self.sort = sort;
+        Ok(self)
     }
 
     /// `set_filter` sets the filtering method in the query.
-    pub fn set_filter(_filter: &str) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_filter(mut self, filter: &str) -> Result<CDXQuerier> {
+        self.filter = filter.into();
+        Ok(self)
     }
 
     /// `set_field` sets the field to be returned if only one is required.
-    pub fn set_field(_field: &str) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_field(mut self, field: &str) -> Result<CDXQuerier> {
+        self.field = Some(field.into());
+        Ok(self)
     }
 
     /// `set_page` sets the page to be returned by the query.
-    pub fn set_page(_page: u64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_page(mut self, page: u64) -> Result<CDXQuerier> {
+        self.page = page;
+        Ok(self)
     }
 
     /// `set_page_size` sets the maximum size per page.
-    pub fn set_page_size(_page_size: u64) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_page_size(mut self, page_size: u64) -> Result<CDXQuerier> {
+        self.page_size = page_size;
+        Ok(self)
     }
 
-    /*
     /// `set_show_num_pages` sets if the query should return the number of pages.
-    pub fn set_show_num_pages(_toggle: bool) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_show_num_pages(mut self, toggle: bool) -> Result<CDXQuerier> {
+        self.show_num_pages = toggle;
+        Ok(self)
     }
 
     /// `set_show_paged_index` sets if the query should return the
     /// secondary index data instead of the CDX data.
-    pub fn set_show_paged_index(_toggle: bool) -> Result<CDXQuerier> {
-        unreachable!()
+    pub fn set_show_paged_index(mut self, toggle: bool) -> Result<CDXQuerier> {
+        self.show_paged_index = toggle;
+        Ok(self)
+    }
+
+    /// `query_string` serializes the set fields into a CDX API query
+    /// string, prefixed by the collection index endpoint set via
+    /// `set_index`/`for_collection`.
+    fn query_string(&self) -> Result<String> {
+        if self.index.is_empty() {
+            return Err(CommonerError::Other("missing collection index; set it with set_index or for_collection".into()));
+        }
+
+        let mut params = vec![("url".to_string(), self.path.clone()), ("output".to_string(), "json".to_string())];
+
+        if self.from != 0 {
+            params.push(("from".to_string(), self.from.to_string()));
+        }
+
+        if self.to != 0 {
+            params.push(("to".to_string(), self.to.to_string()));
+        }
+
+        if self.limit != 0 {
+            params.push(("limit".to_string(), self.limit.to_string()));
+        }
+
+        if self.sort != 0 {
+            params.push(("sort".to_string(), self.sort.to_string()));
+        }
+
+        if !self.filter.is_empty() {
+            params.push(("filter".to_string(), self.filter.clone()));
+        }
+
+        if let Some(field) = &self.field {
+            params.push(("fl".to_string(), field.clone()));
+        }
+
+        if self.page != 0 {
+            params.push(("page".to_string(), self.page.to_string()));
+        }
+
+        if self.page_size != 0 {
+            params.push(("pageSize".to_string(), self.page_size.to_string()));
+        }
+
+        if self.show_num_pages {
+            params.push(("showNumPages".to_string(), "true".to_string()));
+        }
+
+        if self.show_paged_index {
+            params.push(("showPagedIndex".to_string(), "true".to_string()));
+        }
+
+        Ok(format!("{}?{}", self.index, encode_query_params(&params)))
     }
-    */
 
-    /// `exec` execs the `CDXQuerier`.
+    /// `exec` execs the `CDXQuerier` against its collection's CDX API endpoint.
     pub fn exec(self) -> Result<CDXItems> {
-        unreachable!()
+        let url = Url::CDX { path: self.query_string()? };
+
+        let fetcher = Fetcher::json_fetcher(url);
+
+        let contents = fetcher.exec()?;
+
+        CDXItems::from_ndjson(&contents)
+    }
+
+    /// `fetch_num_pages` issues a `showNumPages` request and returns the
+    /// total number of pages available for this query.
+    fn fetch_num_pages(&self) -> Result<u64> {
+        let mut querier = self.clone();
+        querier.show_num_pages = true;
+
+        let url = Url::CDX { path: querier.query_string()? };
+
+        let fetcher = Fetcher::json_fetcher(url);
+
+        let contents = fetcher.exec()?;
+
+        let response: NumPagesResponse = serde_json::from_slice(&contents)?;
+
+        Ok(response.pages)
+    }
+
+    /// `pages` returns a `CDXPages` iterator that lazily walks every page
+    /// of results for this query, one page per `next` call, starting by
+    /// asking the CDX server how many pages there are.
+    pub fn pages(self) -> CDXPages {
+        match self.fetch_num_pages() {
+            Ok(num_pages) => CDXPages { querier: self, page: 0, num_pages, init_err: None },
+            Err(e) => CDXPages { querier: self, page: 0, num_pages: 0, init_err: Some(e) },
+        }
+    }
+}
+
+impl<'a> ToJson<'a> for CDXQuerier {}
+
+impl<'a> FromJson<'a> for CDXQuerier {}
+
+/// `CDXPages` lazily walks every page of a `CDXQuerier` query, fetching
+/// one page at a time as the iterator is advanced, so that large crawl
+/// captures don't need to be materialized all at once.
+pub struct CDXPages {
+    querier: CDXQuerier,
+    page: u64,
+    num_pages: u64,
+    init_err: Option<CommonerError>,
+}
+
+impl CDXPages {
+    /// `collect_all` concatenates every remaining page into a single
+    /// `CDXItems`, stopping once `cap` items have been collected.
+    pub fn collect_all(self, cap: u64) -> Result<CDXItems> {
+        let mut items = Vec::new();
+
+        for page in self {
+            let page = page?;
+            items.extend(page.0);
+
+            if items.len() as u64 >= cap {
+                items.truncate(cap as usize);
+                break;
+            }
+        }
+
+        Ok(CDXItems(items))
+    }
+}
+
+impl Iterator for CDXPages {
+    type Item = Result<CDXItems>;
+
+    fn next(&mut self) -> Option<Result<CDXItems>> {
+        if let Some(err) = self.init_err.take() {
+            return Some(Err(err));
+        }
+
+        if self.page >= self.num_pages {
+            return None;
+        }
+
+        let mut querier = self.querier.clone();
+        querier.page = self.page;
+        self.page += 1;
+
+        Some(querier.exec())
     }
 }
 
@@ -358,28 +782,132 @@ impl<'a> ToJson<'a> for CollectionsInfo {}
 
 impl<'a> FromJson<'a> for CollectionsInfo {}
 
-/// `CDXItem` is a single item returned by a CDX query.
+/// `CDXItem` is a single item returned by a CDX query. The CDX API's
+/// `output=json` serializes every value as a JSON string regardless of
+/// its logical type, and omits fields excluded by a query's `fl`
+/// parameter, so the numeric fields are coerced from strings on
+/// deserialization and every field defaults when CC leaves it out.
 #[derive(Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
 pub struct CDXItem {
+    #[serde(default)]
     pub urlkey: String,
+    #[serde(default, deserialize_with = "u64_from_str")]
     pub timestamp: u64,
+    #[serde(default)]
     pub mime: String,
+    #[serde(default, deserialize_with = "u64_from_str")]
     pub length: u64,
+    #[serde(default, deserialize_with = "u64_from_str")]
     pub status: u64,
+    #[serde(default)]
     pub filename: String,
+    #[serde(default)]
     pub languages: String,
+    #[serde(default)]
     pub charset: String,
+    #[serde(default)]
     pub url: String,
+    #[serde(default, rename = "mime-detected")]
     pub mime_detected: String,
+    #[serde(default, deserialize_with = "u64_from_str")]
     pub offset: u64,
+    #[serde(default)]
     pub digest: String,
 }
 
+/// `u64_from_str` deserializes a `u64` from the JSON string the CDX API
+/// uses for every numeric field under `output=json`.
+fn u64_from_str<'de, D>(deserializer: D) -> std::result::Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse::<u64>().map_err(serde::de::Error::custom)
+}
+
 impl CDXItem {
     /// `new` creates a new `CDXItem`.
     pub fn new() -> CDXItem {
         CDXItem::default()
     }
+
+    /// `verify` decodes this item's `digest` and checks it against a
+    /// hash of `payload`, catching a truncated or corrupted WARC range
+    /// fetch before the caller trusts the payload.
+    pub fn verify(&self, payload: &[u8]) -> Result<bool> {
+        let digest = MultihashDigest::from_string(&self.digest)?;
+        Ok(digest.matches(payload))
+    }
+}
+
+/// `MultihashCode` identifies the hash algorithm backing a
+/// `MultihashDigest`. Common Crawl digests are SHA-1 today, but the set
+/// is kept open so a future SHA-256 (or other) scheme needs only a new
+/// variant here, not a rewrite of the verification code path.
+#[non_exhaustive]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum MultihashCode {
+    Sha1,
+    Sha256,
+}
+
+impl MultihashCode {
+    /// `hash` hashes `payload` with this algorithm.
+    fn hash(self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            MultihashCode::Sha1 => {
+                let mut hasher = Sha1::new();
+                hasher.update(payload);
+                hasher.finalize().to_vec()
+            },
+            MultihashCode::Sha256 => {
+                let mut hasher = Sha256::new();
+                hasher.update(payload);
+                hasher.finalize().to_vec()
+            },
+        }
+    }
+}
+
+/// `MultihashDigest` is a decoded multibase-encoded multihash digest, as
+/// published in a `CDXItem`'s `digest` field.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MultihashDigest {
+    pub code: MultihashCode,
+    pub bytes: Vec<u8>,
+}
+
+impl MultihashDigest {
+    /// `from_string` decodes a Common Crawl digest string. A leading `b`
+    /// is the multibase prefix for base32 and is stripped; a bare value
+    /// with no recognised multibase prefix is assumed to already be
+    /// base32, which is what older crawls publish. The algorithm is
+    /// selected from the decoded length: 20 bytes is SHA-1 (the only
+    /// code Common Crawl emits today), 32 bytes is SHA-256, and any
+    /// other length is rejected as an `InvalidDigest`.
+    pub fn from_string(s: &str) -> Result<MultihashDigest> {
+        let base = match s.strip_prefix('b') {
+            Some(rest) => rest,
+            None => s,
+        };
+
+        let bytes = base32::decode(base32::Alphabet::RFC4648 { padding: false }, base)
+            .ok_or_else(|| CommonerError::InvalidDigest(format!("invalid base32 digest '{}'", s)))?;
+
+        let code = match bytes.len() {
+            20 => MultihashCode::Sha1,
+            32 => MultihashCode::Sha256,
+            n => return Err(CommonerError::InvalidDigest(format!("digest '{}' has unsupported length {}", s, n))),
+        };
+
+        Ok(MultihashDigest { code, bytes })
+    }
+
+    /// `matches` hashes `payload` with this digest's algorithm and
+    /// compares the raw digest bytes.
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        self.code.hash(payload) == self.bytes
+    }
 }
 
 impl<'a> ToJson<'a> for CDXItem {}
@@ -396,6 +924,20 @@ impl CDXItems {
         CDXItems::default()
     }
 
+    /// `from_ndjson` parses the newline-delimited JSON returned by the
+    /// CDX API into `CDXItems`, skipping blank lines.
+    pub fn from_ndjson(b: &[u8]) -> Result<CDXItems> {
+        let text = std::str::from_utf8(b)
+            .map_err(|e| CommonerError::Other(format!("{}", e)))?;
+
+        let items = text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(CDXItem::from_json_string)
+            .collect::<Result<Vec<CDXItem>>>()?;
+
+        Ok(CDXItems(items))
+    }
+
     /// `fetch` fetches `CDXItems` from remote.
     pub fn fetch(path: &str) -> Result<CDXItems> {
         let path = if path.chars().nth(0) == Some('/') {
@@ -419,3 +961,324 @@ impl CDXItems {
 impl<'a> ToJson<'a> for CDXItems {}
 
 impl<'a> FromJson<'a> for CDXItems {}
+
+/// `Encoding` is the compression encoding a whole WARC record (headers and
+/// body together) is stored under.
+/// Common Crawl WARCs are gzip-compressed per-record, but other archives
+/// built on the same format may use a different encoding.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// `decompress` decompresses `data` according to the `Encoding`.
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut decoder = GzDecoder::new(data);
+                let mut out = Vec::new();
+                decoder.read_to_end(&mut out)
+                    .map_err(|e| CommonerError::Other(format!("{}", e)))?;
+                Ok(out)
+            },
+            Encoding::Identity => Ok(data.to_vec()),
+        }
+    }
+}
+
+/// `WarcRecord` is a single WARC record split into its three constituent
+/// parts: the WARC header block, the captured HTTP response headers, and
+/// the payload body, each separated by a blank CRLF line in the record.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct WarcRecord {
+    pub warc_headers: String,
+    pub http_headers: String,
+    pub body: Vec<u8>,
+}
+
+impl WarcRecord {
+    /// `new` creates a new `WarcRecord`.
+    pub fn new() -> WarcRecord {
+        WarcRecord::default()
+    }
+
+    /// `from_block` splits a decompressed WARC record block into its
+    /// header block, captured HTTP headers, and body.
+    pub fn from_block(block: &[u8]) -> Result<WarcRecord> {
+        let sep = b"\r\n\r\n";
+
+        let headers_end = find_subslice(block, sep)
+            .ok_or_else(|| CommonerError::Other("missing warc header terminator".to_string()))?;
+
+        let (warc_headers, rest) = block.split_at(headers_end);
+        let rest = &rest[sep.len()..];
+
+        let warc_headers = String::from_utf8(warc_headers.to_vec())
+            .map_err(|e| CommonerError::Other(format!("{}", e)))?;
+
+        let (http_headers, body) = match find_subslice(rest, sep) {
+            Some(http_headers_end) => {
+                let (http_headers, body) = rest.split_at(http_headers_end);
+                let body = &body[sep.len()..];
+
+                let http_headers = String::from_utf8(http_headers.to_vec())
+                    .map_err(|e| CommonerError::Other(format!("{}", e)))?;
+
+                (http_headers, body.to_vec())
+            },
+            None => (String::new(), rest.to_vec()),
+        };
+
+        Ok(WarcRecord {
+            warc_headers,
+            http_headers,
+            body,
+        })
+    }
+}
+
+/// `find_subslice` returns the index of the first occurrence of `needle`
+/// in `haystack`, if any.
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// `WarcFetcher` is used to fetch a single WARC record from a remote
+/// Common Crawl WARC file via an HTTP Range request, decompressing and
+/// splitting it into a `WarcRecord`.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct WarcFetcher {
+    pub path: String,
+    pub offset: u64,
+    pub length: u64,
+    pub encoding: Encoding,
+    pub digest: Option<String>,
+}
+
+impl WarcFetcher {
+    /// `new` creates a new `WarcFetcher`.
+    pub fn new() -> WarcFetcher {
+        WarcFetcher::default()
+    }
+
+    /// `from_cdx_item` creates a `WarcFetcher` for the WARC record
+    /// referenced by `item`. The item's digest is carried along so
+    /// `exec` can verify the fetched record against it.
+    pub fn from_cdx_item(item: &CDXItem) -> WarcFetcher {
+        WarcFetcher {
+            path: item.filename.clone(),
+            offset: item.offset,
+            length: item.length,
+            encoding: Encoding::default(),
+            digest: Some(item.digest.clone()),
+        }
+    }
+
+    /// `exec` execs the `WarcFetcher`, returning the decompressed and
+    /// split `WarcRecord`. When `digest` is set, it is checked against
+    /// the record's `body` only, since the CDX `digest` field (the
+    /// WARC-Payload-Digest) never covers the WARC header block or the
+    /// captured HTTP headers.
+    pub fn exec(self) -> Result<WarcRecord> {
+        if self.length == 0 {
+            return Err(CommonerError::Other("invalid range: zero length".into()));
+        }
+
+        let url = Url::WARC { path: self.path.clone() };
+        let range = format!("bytes={}-{}", self.offset, self.offset + self.length - 1);
+        let range_value = HeaderValue::from_str(&range)?;
+
+        let mut res = Client::new()
+            .get(&url.to_string())
+            .header(RANGE, range_value)
+            .send()?;
+
+        if res.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(CommonerError::Http { status: res.status() });
+        }
+
+        let mut contents = Vec::new();
+        res.copy_to(&mut contents)?;
+
+        let block = self.encoding.decompress(&contents)?;
+        let record = WarcRecord::from_block(&block)?;
+
+        if let Some(digest) = &self.digest {
+            let digest = MultihashDigest::from_string(digest)?;
+
+            if !digest.matches(&record.body) {
+                return Err(CommonerError::Other("fetched WARC record failed digest verification".into()));
+            }
+        }
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warc_record_from_block_splits_headers_and_body() {
+        let block = b"WARC/1.0\r\nWARC-Type: response\r\n\r\nHTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html></html>";
+
+        let record = WarcRecord::from_block(block).unwrap();
+
+        assert_eq!(record.warc_headers, "WARC/1.0\r\nWARC-Type: response");
+        assert_eq!(record.http_headers, "HTTP/1.1 200 OK\r\nContent-Type: text/html");
+        assert_eq!(record.body, b"<html></html>");
+    }
+
+    #[test]
+    fn warc_record_from_block_without_http_headers() {
+        let block = b"WARC/1.0\r\nWARC-Type: warcinfo\r\n\r\nnot an http response";
+
+        let record = WarcRecord::from_block(block).unwrap();
+
+        assert_eq!(record.warc_headers, "WARC/1.0\r\nWARC-Type: warcinfo");
+        assert_eq!(record.http_headers, "");
+        assert_eq!(record.body, b"not an http response");
+    }
+
+    #[test]
+    fn pad_timestamp_pads_lower_and_upper_bound() {
+        assert_eq!(pad_timestamp(2020, b'0').unwrap(), 20200000000000);
+        assert_eq!(pad_timestamp(2020, b'9').unwrap(), 20209999999999);
+        assert_eq!(pad_timestamp(20200101000000, b'0').unwrap(), 20200101000000);
+    }
+
+    #[test]
+    fn pad_timestamp_rejects_too_many_digits() {
+        assert!(pad_timestamp(123456789012345, b'0').is_err());
+    }
+
+    #[test]
+    fn cdx_querier_query_string_targets_collection_index() {
+        let querier = CDXQuerier::new()
+            .set_index("CC-MAIN-2024-10-index").unwrap()
+            .set_path("example.com").unwrap()
+            .set_limit(5).unwrap();
+
+        let query_string = querier.query_string().unwrap();
+
+        assert!(query_string.starts_with("CC-MAIN-2024-10-index?"));
+        assert!(query_string.contains("url=example.com"));
+        assert!(query_string.contains("output=json"));
+        assert!(query_string.contains("limit=5"));
+    }
+
+    #[test]
+    fn cdx_querier_query_string_percent_encodes_values() {
+        let querier = CDXQuerier::new()
+            .set_index("CC-MAIN-2024-10-index").unwrap()
+            .set_path("example.com/foo bar").unwrap()
+            .set_filter("status:200&mime:text/html").unwrap();
+
+        let query_string = querier.query_string().unwrap();
+        let (index, query) = query_string.split_once('?').unwrap();
+
+        assert_eq!(index, "CC-MAIN-2024-10-index");
+        assert!(!query.contains(' '), "raw space would be malformed in a query string: {}", query);
+
+        let url = reqwest::Url::parse(&format!("http://cdx.invalid/?{}", query)).unwrap();
+        let pairs: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(pairs.get("url").unwrap(), "example.com/foo bar");
+        assert_eq!(pairs.get("filter").unwrap(), "status:200&mime:text/html");
+    }
+
+    #[test]
+    fn cdx_querier_query_string_requires_index() {
+        let querier = CDXQuerier::new().set_path("example.com").unwrap();
+
+        assert!(querier.query_string().is_err());
+    }
+
+    #[test]
+    fn cdx_querier_for_collection_parses_index_from_cdx_api() {
+        let mut info = CollectionInfo::new();
+        info.cdx_api = "https://index.commoncrawl.org/CC-MAIN-2024-10-index".to_string();
+
+        let querier = CDXQuerier::for_collection(&info).unwrap();
+
+        assert_eq!(querier.index, "CC-MAIN-2024-10-index");
+    }
+
+    #[test]
+    fn num_pages_response_parses_show_num_pages_json_object() {
+        let response: NumPagesResponse = serde_json::from_str(
+            r#"{"pages": 38, "pageSize": 5, "blocks": 38}"#
+        ).unwrap();
+
+        assert_eq!(response.pages, 38);
+    }
+
+    #[test]
+    fn content_type_from_string_is_case_insensitive() {
+        assert_eq!(ContentType::from_string("Application/JSON").unwrap(), ContentType::JSON);
+        assert_eq!(ContentType::from_string("APPLICATION/X-GZIP").unwrap(), ContentType::GZIP);
+    }
+
+    #[test]
+    fn content_type_from_string_reads_charset_parameter() {
+        let content_type = ContentType::from_string("text/plain; charset=UTF-16").unwrap();
+        assert_eq!(content_type, ContentType::TEXT { charset: Charset::UTF16 });
+    }
+
+    #[test]
+    fn content_type_from_string_defaults_charset_for_text_plain() {
+        let content_type = ContentType::from_string("text/plain").unwrap();
+        assert_eq!(content_type, ContentType::TEXT { charset: Charset::UTF8 });
+    }
+
+    #[test]
+    fn content_type_from_string_falls_back_to_other() {
+        let content_type = ContentType::from_string("font/woff2").unwrap();
+        assert_eq!(content_type, ContentType::Other("font/woff2".to_string()));
+    }
+
+    #[test]
+    fn content_type_from_string_rejects_empty_essence() {
+        assert!(ContentType::from_string("").is_err());
+        assert!(ContentType::from_string("; charset=utf-8").is_err());
+    }
+
+    #[test]
+    fn multihash_digest_round_trips_known_cc_digest() {
+        // base32(sha1("hello common crawl")), in the bare uppercase form
+        // Common Crawl actually publishes in `CDXItem::digest`.
+        let digest = MultihashDigest::from_string("C63YSPQS22MIPJS52CX4AEJWWZKRNEI6").unwrap();
+
+        assert_eq!(digest.code, MultihashCode::Sha1);
+        assert!(digest.matches(b"hello common crawl"));
+        assert!(!digest.matches(b"hello commons crawl"));
+    }
+
+    #[test]
+    fn multihash_digest_rejects_invalid_base32() {
+        assert!(MultihashDigest::from_string("not-base32!").is_err());
+    }
+
+    #[test]
+    fn cdx_item_deserializes_a_real_cdx_line() {
+        let line = r#"{"urlkey": "com,example)/", "timestamp": "20240229000000", "url": "http://example.com/", "mime": "text/html", "mime-detected": "text/html", "status": "200", "digest": "3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ", "length": "1256", "offset": "52349844", "filename": "crawl-data/CC-MAIN-2024-10/segments/example/file.warc.gz"}"#;
+
+        let item = CDXItem::from_json_string(line).unwrap();
+
+        assert_eq!(item.urlkey, "com,example)/");
+        assert_eq!(item.timestamp, 20240229000000);
+        assert_eq!(item.url, "http://example.com/");
+        assert_eq!(item.mime_detected, "text/html");
+        assert_eq!(item.status, 200);
+        assert_eq!(item.length, 1256);
+        assert_eq!(item.offset, 52349844);
+        // `languages`/`charset` are absent from this line, as CC omits
+        // them for many captures; they should default rather than error.
+        assert_eq!(item.languages, "");
+        assert_eq!(item.charset, "");
+    }
+}